@@ -1,15 +1,23 @@
 use std::{
-    cmp::max,
-    io::Write,
-    os::unix::process::ExitStatusExt,
+    collections::HashMap,
+    io::{Read, Write},
+    os::unix::process::{CommandExt, ExitStatusExt},
     path::PathBuf,
-    process::{Command, Output},
+    process::{Child, Command, Output, Stdio},
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Context};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use termion::{color, style};
 
+#[derive(Clone, Copy, ValueEnum)]
+enum CompareMode {
+    Exact,
+    Tokens,
+    Float,
+}
+
 #[derive(Parser)]
 pub struct Args {
     #[arg(id = "TESTS", help = "Path to the file with test suite description")]
@@ -17,21 +25,82 @@ pub struct Args {
 
     #[arg(id = "SOLUTION", help = "Command to run the solution")]
     solution_command: String,
+
+    #[arg(
+        long,
+        value_name = "MILLIS",
+        help = "Time limit per test, in milliseconds"
+    )]
+    time_limit: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "Special-judge checker command, invoked as `COMMAND <input> <answer> <output>`"
+    )]
+    checker: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "exact",
+        help = "How to compare the solution's output against the expected answer"
+    )]
+    compare: CompareMode,
+
+    #[arg(
+        long,
+        default_value_t = 1e-6,
+        help = "Absolute-or-relative tolerance used by `--compare float`"
+    )]
+    epsilon: f64,
+
+    #[arg(
+        long,
+        default_value_t = 1,
+        help = "Number of tests to run concurrently"
+    )]
+    jobs: usize,
 }
 
 enum CheckResult {
     Correct,
     Incorrect { message: String },
+    TimeLimitExceeded,
+    RuntimeError { message: String },
 }
 
+#[derive(Clone, Copy)]
 struct Test<'a> {
     input: &'a str,
     answer: &'a str,
+    exit_code: Option<i32>,
+    expected_stderr: Option<&'a str>,
 }
 
 impl<'a> Test<'a> {
-    pub fn new(input: &'a str, answer: &'a str) -> Self {
-        Self { input, answer }
+    pub fn new(
+        input: &'a str,
+        answer: &'a str,
+        exit_code: Option<i32>,
+        expected_stderr: Option<&'a str>,
+    ) -> Self {
+        Self { input, answer, exit_code, expected_stderr }
+    }
+}
+
+/// Splits `rest` (everything after `[answer]\n`) into the answer body and
+/// whatever `[exit]`/`[stderr]` directives follow it, at whichever of
+/// `[exit]` or `[stderr]` appears earliest — the two directives can be
+/// written in either order, so neither can be assumed to come first.
+fn split_off_directives(rest: &str) -> (&str, &str) {
+    let pos = [rest.find("[exit]\n"), rest.find("[stderr]\n")]
+        .into_iter()
+        .flatten()
+        .min();
+    match pos {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, ""),
     }
 }
 
@@ -41,12 +110,46 @@ fn parse_test(s: &str) -> anyhow::Result<Test> {
         None => return Err(anyhow!("`[input]` header is not present")),
     };
 
-    let (input, answer) = match body.split_once("[answer]\n") {
-        Some((i, a)) => (i.trim(), a.trim()),
+    let (input, rest) = match body.split_once("[answer]\n") {
+        Some((i, a)) => (i.trim(), a),
         None => return Err(anyhow!("`[answer]` header is not present")),
     };
 
-    Ok(Test::new(input, answer))
+    let (answer, directives) = split_off_directives(rest);
+    let answer = answer.trim();
+
+    let mut exit_code = None;
+    let stderr_section = match directives.strip_prefix("[exit]\n") {
+        Some(after_exit) => {
+            let (code, rest) = match after_exit.split_once("[stderr]\n") {
+                Some((c, r)) => (c.trim(), Some(r)),
+                None => (after_exit.trim(), None),
+            };
+            exit_code = Some(
+                code.parse()
+                    .with_context(|| format!("invalid `[exit]` code `{code}`"))?,
+            );
+            rest
+        }
+        None => match directives.strip_prefix("[stderr]\n") {
+            Some(after_stderr) => match after_stderr.split_once("[exit]\n") {
+                Some((stderr, code)) => {
+                    let code = code.trim();
+                    exit_code = Some(
+                        code.parse()
+                            .with_context(|| format!("invalid `[exit]` code `{code}`"))?,
+                    );
+                    Some(stderr)
+                }
+                None => Some(after_stderr),
+            },
+            None => None,
+        },
+    };
+
+    let expected_stderr = stderr_section.map(str::trim);
+
+    Ok(Test::new(input, answer, exit_code, expected_stderr))
 }
 
 fn parse_tests(source: &str) -> anyhow::Result<Vec<Test>> {
@@ -57,50 +160,289 @@ fn parse_tests(source: &str) -> anyhow::Result<Vec<Test>> {
         .collect()
 }
 
-fn run_test<'a>(test: Test<'a>, command: &str) -> anyhow::Result<()> {
+fn run_test<'a>(
+    test: Test<'a>,
+    command: &str,
+    time_limit: Option<Duration>,
+    checker: Option<&str>,
+    compare: CompareMode,
+    epsilon: f64,
+) -> anyhow::Result<(CheckResult, Duration)> {
     let mut child = create_solution_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
         .spawn()
         .with_context(|| "Couldn't spawn child process")?;
 
-    child
+    match child
         .stdin
         .take()
         .with_context(|| "Couldn't open child stdin")?
         .write_all(test.input.as_bytes())
-        .with_context(|| "Couldn't write to child stdin")?;
-
-    let output = child
-        .wait_with_output()
-        .with_context(|| "could not read output of the solution")?;
-    report_if_solution_terminated_correctly(&output)?;
+    {
+        Ok(()) => {}
+        // the solution exited (or closed stdin) before reading all of its
+        // input; that's not a harness error, it's for the exit code/answer
+        // checks below to judge
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            return Err(err).with_context(|| "Couldn't write to child stdin")
+        }
+    }
+    // the stdin handle above is dropped (and thus closed) at the end of the
+    // statement, so solutions that read until EOF don't deadlock
+
+    let (output, elapsed) = wait_for_child(child, time_limit)?;
+
+    let result = match output {
+        None => CheckResult::TimeLimitExceeded,
+        Some(output) => match check_exit_status(&output, test.exit_code)? {
+            Some(result) => result,
+            None => {
+                let actual_answer = std::str::from_utf8(&output.stdout)
+                    .with_context(|| "failed to convert solution stdout to UTF-8")?
+                    .trim();
+
+                let stdout_result = match checker {
+                    Some(checker_command) => {
+                        run_external_checker(checker_command, &test, actual_answer)?
+                    }
+                    None => check_answer(test.answer, actual_answer, compare, epsilon),
+                };
+
+                match (stdout_result, test.expected_stderr) {
+                    (CheckResult::Correct, Some(expected_stderr)) => {
+                        let actual_stderr = std::str::from_utf8(&output.stderr)
+                            .with_context(|| {
+                                "failed to convert solution stderr to UTF-8"
+                            })?
+                            .trim();
+
+                        check_answer(expected_stderr, actual_stderr, compare, epsilon)
+                    }
+                    (stdout_result, _) => stdout_result,
+                }
+            }
+        },
+    };
 
-    let actual_answer = std::str::from_utf8(&output.stdout)
-        .with_context(|| "failed to convert solution stdout to UTF-8")?
-        .trim();
+    Ok((result, elapsed))
+}
 
-    match check_lines(test.answer, actual_answer) {
-        CheckResult::Correct => {
-            println!(
-                "{}{}Passed{}{}",
-                style::Bold,
-                color::Fg(color::Green),
-                style::Reset,
-                color::Fg(color::Reset)
-            )
-        }
-        CheckResult::Incorrect { message } => {
-            println!(
-                "{}{}Wrong answer{}{}",
+/// Renders one test's outcome (or the error it produced) into a single
+/// string, in the same format `run_test` used to `println!` directly. Pulled
+/// out so worker threads never write partial, interleaved colored output:
+/// each buffers its own string and the caller prints them in test order.
+fn render_test_result(result: &anyhow::Result<(CheckResult, Duration)>) -> String {
+    match result {
+        Ok((CheckResult::Correct, elapsed)) => format!(
+            "{}{}Passed{}{} ({} ms)\n",
+            style::Bold,
+            color::Fg(color::Green),
+            style::Reset,
+            color::Fg(color::Reset),
+            elapsed.as_millis()
+        ),
+        Ok((CheckResult::Incorrect { message }, elapsed)) => format!(
+            "{}{}Wrong answer{}{} ({} ms)\n{message}",
+            style::Bold,
+            color::Fg(color::Red),
+            style::Reset,
+            color::Fg(color::Reset),
+            elapsed.as_millis()
+        ),
+        Ok((CheckResult::TimeLimitExceeded, elapsed)) => format!(
+            "{}{}Time Limit Exceeded{}{} ({} ms)\n",
+            style::Bold,
+            color::Fg(color::Red),
+            style::Reset,
+            color::Fg(color::Reset),
+            elapsed.as_millis()
+        ),
+        Ok((CheckResult::RuntimeError { message }, elapsed)) => format!(
+            "{}{}Runtime Error{}{} ({} ms)\n{message}",
+            style::Bold,
+            color::Fg(color::Red),
+            style::Reset,
+            color::Fg(color::Reset),
+            elapsed.as_millis()
+        ),
+        Err(err) => {
+            let mut message = format!(
+                "{}{}Error occured{}\n{err}:\n",
                 style::Bold,
                 color::Fg(color::Red),
                 style::Reset,
-                color::Fg(color::Reset)
             );
-            print!("{message}");
+            for cause in err.chain().skip(1) {
+                message.push_str(&format!("{cause}\n"));
+            }
+            message
         }
     }
+}
 
-    Ok(())
+/// Runs `tests` using up to `jobs` worker threads pulling from a shared work
+/// queue, streaming each test's rendered result to stdout in original test
+/// order as soon as it's available (out-of-order completions are held in a
+/// small reorder buffer rather than waiting for the whole suite). Returns
+/// the number of tests that passed.
+fn run_tests<'a>(
+    tests: &[Test<'a>],
+    command: &str,
+    time_limit: Option<Duration>,
+    checker: Option<&str>,
+    compare: CompareMode,
+    epsilon: f64,
+    jobs: usize,
+) -> usize {
+    let next_test = std::sync::atomic::AtomicUsize::new(0);
+    let next_test = &next_test;
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, tests.len().max(1)) {
+            let tx = tx.clone();
+            scope.spawn(move || loop {
+                let i = next_test.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let Some(test) = tests.get(i) else {
+                    break;
+                };
+
+                let result =
+                    run_test(*test, command, time_limit, checker, compare, epsilon);
+                if tx.send((i, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        print_results_as_they_arrive(rx)
+    })
+}
+
+/// Buffers out-of-order `(test index, result)` pairs arriving on `rx` and
+/// prints each one, in order, as soon as its predecessor has printed.
+fn print_results_as_they_arrive(
+    rx: std::sync::mpsc::Receiver<(usize, anyhow::Result<(CheckResult, Duration)>)>,
+) -> usize {
+    let mut pending = HashMap::new();
+    let mut next_to_print = 0;
+    let mut passed = 0;
+
+    for (i, result) in rx {
+        pending.insert(i, result);
+
+        while let Some(result) = pending.remove(&next_to_print) {
+            print!("{}Test {}: {}", style::Bold, next_to_print + 1, style::Reset);
+            print!("{}", render_test_result(&result));
+
+            if matches!(result, Ok((CheckResult::Correct, _))) {
+                passed += 1;
+            }
+
+            next_to_print += 1;
+        }
+    }
+
+    passed
+}
+
+type ReaderHandle = std::thread::JoinHandle<std::io::Result<Vec<u8>>>;
+
+/// Spawns a thread that drains `pipe` into a buffer as data arrives, so a
+/// solution that writes more than the OS pipe buffer can hold doesn't block
+/// on `write()` while we're only waiting for it to exit (or polling for a
+/// time limit) instead of reading.
+fn spawn_reader<R: Read + Send + 'static>(pipe: R) -> ReaderHandle {
+    std::thread::spawn(move || {
+        let mut pipe = pipe;
+        let mut buf = Vec::new();
+        pipe.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+fn join_reader(handle: ReaderHandle, stream_name: &str) -> anyhow::Result<Vec<u8>> {
+    handle
+        .join()
+        .map_err(|_| anyhow!("the {stream_name} reader thread panicked"))?
+        .with_context(|| format!("failed to read the solution's {stream_name}"))
+}
+
+/// Waits for `child` to exit, killing its process group with `SIGKILL` if it
+/// is still running once `time_limit` elapses. Returns `None` in place of the
+/// `Output` when the child was killed for exceeding the time limit, alongside
+/// the wall-clock time spent waiting.
+fn wait_for_child(
+    mut child: Child,
+    time_limit: Option<Duration>,
+) -> anyhow::Result<(Option<Output>, Duration)> {
+    let start = Instant::now();
+
+    // drain stdout/stderr on dedicated threads as the child produces them;
+    // otherwise a chatty solution blocks on a full pipe buffer while we're
+    // only waiting/polling for exit, which looks indistinguishable from a
+    // genuine timeout
+    let stdout_reader =
+        spawn_reader(child.stdout.take().expect("stdout was piped"));
+    let stderr_reader =
+        spawn_reader(child.stderr.take().expect("stderr was piped"));
+
+    let Some(time_limit) = time_limit else {
+        let status = child
+            .wait()
+            .with_context(|| "could not wait for the solution to exit")?;
+        let stdout = join_reader(stdout_reader, "stdout")?;
+        let stderr = join_reader(stderr_reader, "stderr")?;
+        return Ok((
+            Some(Output { status, stdout, stderr }),
+            start.elapsed(),
+        ));
+    };
+
+    let deadline = start + time_limit;
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|| "failed to poll the solution's status")?
+        {
+            break Some(status);
+        }
+
+        if Instant::now() >= deadline {
+            // the child was spawned as the leader of its own process group
+            // (`process_group(0)`), so killing the group cannot affect
+            // anything outside of it
+            unsafe {
+                libc::kill(-(child.id() as i32), libc::SIGKILL);
+            }
+            let _ = child.wait();
+            break None;
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    let elapsed = start.elapsed();
+
+    match status {
+        Some(status) => {
+            let stdout = join_reader(stdout_reader, "stdout")?;
+            let stderr = join_reader(stderr_reader, "stderr")?;
+            Ok((Some(Output { status, stdout, stderr }), elapsed))
+        }
+        None => {
+            // the child is gone either way; drain the reader threads so they
+            // don't outlive it, but the time limit verdict doesn't need what
+            // they read
+            let _ = join_reader(stdout_reader, "stdout");
+            let _ = join_reader(stderr_reader, "stderr");
+            Ok((None, elapsed))
+        }
+    }
 }
 
 pub fn run(args: Args) -> anyhow::Result<()> {
@@ -116,23 +458,25 @@ pub fn run(args: Args) -> anyhow::Result<()> {
             )
         })?;
     let tests = parse_tests(&tests_source)?;
-
-    for (i, test) in tests.into_iter().enumerate() {
-        print!("{}Test {}: {}", style::Bold, i + 1, style::Reset);
-        match run_test(test, args.solution_command.trim()) {
-            Ok(_) => {}
-            Err(err) => {
-                println!(
-                    "{}{}Error occured{}",
-                    style::Bold,
-                    color::Fg(color::Red),
-                    style::Reset,
-                );
-                println!("{err}:");
-                err.chain().skip(1).for_each(|cause| println!("{}", cause));
-            }
-        }
-    }
+    let time_limit = args.time_limit.map(Duration::from_millis);
+    let checker = args.checker.as_deref();
+
+    let passed = run_tests(
+        &tests,
+        args.solution_command.trim(),
+        time_limit,
+        checker,
+        args.compare,
+        args.epsilon,
+        args.jobs,
+    );
+
+    println!(
+        "{}{passed}/{}{} passed",
+        style::Bold,
+        tests.len(),
+        style::Reset
+    );
 
     Ok(())
 }
@@ -146,26 +490,51 @@ fn create_solution_command(command: &str) -> Command {
     solution_command
 }
 
-fn report_if_solution_terminated_correctly(
+/// Checks the child's exit status, either against an explicit `[exit]`
+/// directive or, by default, against plain success. Returns `Some` with a
+/// verdict when the mismatch is itself the outcome to report (a wrong exit
+/// code, or a segfault), or `None` to mean "status is fine, keep checking
+/// the answer".
+fn check_exit_status(
     output: &Output,
-) -> Result<(), anyhow::Error> {
+    expected_exit_code: Option<i32>,
+) -> anyhow::Result<Option<CheckResult>> {
+    if let Some(expected) = expected_exit_code {
+        let actual = output.status.code();
+        return Ok(if actual == Some(expected) {
+            None
+        } else {
+            let got = match actual {
+                Some(code) => code.to_string(),
+                None => format!(
+                    "terminated by signal {}",
+                    output.status.signal().unwrap_or(0)
+                ),
+            };
+            Some(CheckResult::Incorrect {
+                message: format!(
+                    "exit code mismatch: got {got}, expected {expected}\n"
+                ),
+            })
+        });
+    }
+
     if output.status.success() {
-        return Ok(());
+        return Ok(None);
     }
 
     if let Some(libc::SIGSEGV) = output.status.signal() {
-        Err(anyhow!("Segmentation fault"))
-    } else {
-        Err(anyhow!(
-            "{}",
-            std::str::from_utf8(&output.stderr).with_context(|| {
-                "failed to convert solution stderr to UTF-8"
-            })?
-        ))
-    }
-    .with_context(|| {
-        "Solution terminated with a non-zero exit code".to_string()
-    })
+        return Ok(Some(CheckResult::RuntimeError {
+            message: "Segmentation fault\n".to_string(),
+        }));
+    }
+
+    Err(anyhow!(
+        "{}",
+        std::str::from_utf8(&output.stderr)
+            .with_context(|| "failed to convert solution stderr to UTF-8")?
+    ))
+    .with_context(|| "Solution terminated with a non-zero exit code".to_string())
 }
 
 fn trim_filter_non_empty(mut line: &str) -> Option<&str> {
@@ -177,57 +546,398 @@ fn trim_filter_non_empty(mut line: &str) -> Option<&str> {
     }
 }
 
-fn get_integer_length(mut n: usize) -> usize {
-    let mut result = 0;
-    while n > 0 {
-        n /= 10;
-        result += 1;
+/// Invokes an external special-judge checker, in the style of testlib-based
+/// judges: the test input, the expected answer and the solution's actual
+/// output are each written to a temp file, and the checker is run as
+/// `checker_command <input> <answer> <output>`. A zero exit code means
+/// Accepted, a nonzero one means Wrong Answer with the checker's stdout
+/// followed by its stderr as the diagnostic message; on Accepted the
+/// checker's output is not reported.
+fn run_external_checker(
+    checker_command: &str,
+    test: &Test,
+    actual_answer: &str,
+) -> anyhow::Result<CheckResult> {
+    let input_path = write_temp_file("input", test.input)?;
+    let answer_path = write_temp_file("answer", test.answer)?;
+    let output_path = write_temp_file("output", actual_answer)?;
+
+    let result = create_solution_command(checker_command)
+        .arg(&input_path)
+        .arg(&answer_path)
+        .arg(&output_path)
+        .output()
+        .with_context(|| "Couldn't run the checker");
+
+    let _ = std::fs::remove_file(&input_path);
+    let _ = std::fs::remove_file(&answer_path);
+    let _ = std::fs::remove_file(&output_path);
+
+    let output = result?;
+    let mut message = String::from_utf8_lossy(&output.stdout).into_owned();
+    message.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    if output.status.success() {
+        Ok(CheckResult::Correct)
+    } else {
+        Ok(CheckResult::Incorrect { message })
     }
-    return result;
 }
 
+/// Writes `contents` to a fresh, uniquely-named file under the system temp
+/// directory, for handing off to an external checker process.
+fn write_temp_file(label: &str, contents: &str) -> anyhow::Result<PathBuf> {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    let path = std::env::temp_dir()
+        .join(format!("checker-{}-{id}-{label}", std::process::id()));
+    std::fs::write(&path, contents)
+        .with_context(|| format!("Couldn't write temp file `{}`", path.display()))?;
+    Ok(path)
+}
+
+fn check_answer(
+    correct_answer: &str,
+    actual_answer: &str,
+    compare: CompareMode,
+    epsilon: f64,
+) -> CheckResult {
+    match compare {
+        CompareMode::Exact => check_lines(correct_answer, actual_answer),
+        CompareMode::Tokens => check_tokens(correct_answer, actual_answer, None),
+        CompareMode::Float => check_tokens(correct_answer, actual_answer, Some(epsilon)),
+    }
+}
+
+/// Compares `correct_answer` and `actual_answer` as whitespace-separated
+/// token streams, ignoring line structure. When `epsilon` is `Some`, tokens
+/// that both parse as `f64` are additionally accepted as equal within that
+/// absolute-or-relative tolerance; everything else falls back to exact
+/// string comparison.
+fn check_tokens(
+    correct_answer: &str,
+    actual_answer: &str,
+    epsilon: Option<f64>,
+) -> CheckResult {
+    let correct_tokens: Vec<&str> = correct_answer.split_whitespace().collect();
+    let actual_tokens: Vec<&str> = actual_answer.split_whitespace().collect();
+
+    if correct_tokens.len() != actual_tokens.len() {
+        return CheckResult::Incorrect {
+            message: format!(
+                "token count mismatch: expected {} token(s), got {}\n",
+                correct_tokens.len(),
+                actual_tokens.len()
+            ),
+        };
+    }
+
+    for (i, (correct, actual)) in
+        correct_tokens.iter().zip(actual_tokens.iter()).enumerate()
+    {
+        if !tokens_equal(correct, actual, epsilon) {
+            return CheckResult::Incorrect {
+                message: format!(
+                    "token {} differs: got `{actual}` => expected `{correct}`\n",
+                    i + 1
+                ),
+            };
+        }
+    }
+
+    CheckResult::Correct
+}
+
+fn tokens_equal(expected: &str, actual: &str, epsilon: Option<f64>) -> bool {
+    if expected == actual {
+        return true;
+    }
+
+    let Some(epsilon) = epsilon else {
+        return false;
+    };
+
+    match (expected.parse::<f64>(), actual.parse::<f64>()) {
+        (Ok(e), Ok(a)) => floats_close(e, a, epsilon),
+        _ => false,
+    }
+}
+
+fn floats_close(a: f64, b: f64, epsilon: f64) -> bool {
+    if a.is_nan() || b.is_nan() {
+        return false;
+    }
+    if a.is_infinite() || b.is_infinite() {
+        return a == b;
+    }
+
+    let diff = (a - b).abs();
+    diff <= epsilon || diff <= epsilon * a.abs().max(b.abs())
+}
+
+#[derive(Debug, PartialEq)]
+enum LineDiffOp<'a> {
+    Equal(&'a str),
+    OnlyInActual(&'a str),
+    OnlyInExpected(&'a str),
+}
+
+/// Above this combined line count, Myers's trace (a full copy of `V`, sized
+/// by the combined line count, for every edit distance `d`, so O((N+M)*D) in
+/// both time and memory) gets too slow for a harness that should report "very wrong
+/// answer" quickly; fall back to a plain positional compare instead.
+const MAX_ALIGNED_DIFF_LINES: usize = 4000;
+
 fn check_lines(correct_answer: &str, actual_answer: &str) -> CheckResult {
-    let mut message = String::new();
-    let mut correct = true;
-    let mut correct_lines =
-        correct_answer.lines().filter_map(trim_filter_non_empty);
-    let mut actual_lines =
-        actual_answer.lines().filter_map(trim_filter_non_empty);
+    let correct_lines: Vec<&str> =
+        correct_answer.lines().filter_map(trim_filter_non_empty).collect();
+    let actual_lines: Vec<&str> =
+        actual_answer.lines().filter_map(trim_filter_non_empty).collect();
+
+    if correct_lines == actual_lines {
+        return CheckResult::Correct;
+    }
+
+    let ops = if correct_lines.len() + actual_lines.len() > MAX_ALIGNED_DIFF_LINES {
+        diff_lines_positional(&correct_lines, &actual_lines)
+    } else {
+        diff_lines(&correct_lines, &actual_lines)
+    };
+
+    let message = ops
+        .into_iter()
+        .map(|op| match op {
+            LineDiffOp::Equal(line) => format!("  {line}\n"),
+            LineDiffOp::OnlyInActual(line) => format!(
+                "{}{}- got      {line}{}{}\n",
+                style::Bold,
+                color::Fg(color::Red),
+                style::Reset,
+                color::Fg(color::Reset)
+            ),
+            LineDiffOp::OnlyInExpected(line) => format!(
+                "{}{}+ expected {line}{}{}\n",
+                style::Bold,
+                color::Fg(color::Green),
+                style::Reset,
+                color::Fg(color::Reset)
+            ),
+        })
+        .collect();
+
+    CheckResult::Incorrect { message }
+}
 
-    let max_line_count =
-        max(correct_lines.clone().count(), actual_lines.clone().count());
+/// Compares `expected` and `actual` line-by-line at the same index, without
+/// trying to realign around insertions/deletions. Used in place of
+/// `diff_lines` once the sequences are too large for its trace to stay
+/// fast — a misaligned diff that returns quickly beats an aligned one that
+/// doesn't.
+fn diff_lines_positional<'a>(
+    expected: &[&'a str],
+    actual: &[&'a str],
+) -> Vec<LineDiffOp<'a>> {
+    let len = expected.len().max(actual.len());
+    let mut ops = Vec::with_capacity(len);
+
+    for i in 0..len {
+        match (expected.get(i), actual.get(i)) {
+            (Some(&e), Some(&a)) if e == a => ops.push(LineDiffOp::Equal(e)),
+            (Some(&e), Some(&a)) => {
+                ops.push(LineDiffOp::OnlyInActual(a));
+                ops.push(LineDiffOp::OnlyInExpected(e));
+            }
+            (Some(&e), None) => ops.push(LineDiffOp::OnlyInExpected(e)),
+            (None, Some(&a)) => ops.push(LineDiffOp::OnlyInActual(a)),
+            (None, None) => unreachable!("i is bounded by the longer side's len"),
+        }
+    }
 
-    let max_line_number_len = get_integer_length(max_line_count);
+    ops
+}
 
-    for i in 1..=max_line_count {
-        let cur_line = actual_lines.next().unwrap_or("");
-        let cur_correct_line = correct_lines.next().unwrap_or("");
+/// Computes a line-level edit script between `expected` and `actual` using
+/// the Myers shortest-edit-script algorithm: for increasing edit distance
+/// `d`, it finds the furthest-reaching point reachable on each diagonal `k`
+/// (choosing between an insertion from `actual` and a deletion from
+/// `expected`), snapshotting the reach (`V`) at every `d` so the script can
+/// be recovered by backtracking from the end once the sequences meet.
+///
+/// `V` is stored as a flat `Vec` indexed by `k + max` rather than a
+/// `HashMap<isize, isize>`, since it's cloned into `trace` on every step of
+/// `d` — a `Vec` clone is a single `memcpy`, not `max` individual hash
+/// insertions.
+fn diff_lines<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<LineDiffOp<'a>> {
+    let n = expected.len() as isize;
+    let m = actual.len() as isize;
+    let max = n + m;
+    let index = |k: isize| (k + max) as usize;
+
+    let mut v = vec![0isize; 2 * max as usize + 1];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && expected[x as usize] == actual[y as usize] {
+                x += 1;
+                y += 1;
+            }
 
-        let mut cur_line_number_formatted = String::new();
+            v[index(k)] = x;
 
-        // offset line numbers to appear evenly
-        for _ in 0..max_line_number_len {
-            cur_line_number_formatted.push(' ');
+            if x >= n && y >= m {
+                break 'search;
+            }
         }
+    }
 
-        cur_line_number_formatted.push_str(&format!("{i} "));
-
-        if cur_line != cur_correct_line {
-            correct = false;
-            message.push_str(&format!(
-                "{} {} {cur_line} {} => expected {} {cur_correct_line} {}\n",
-                cur_line_number_formatted,
-                color::Bg(color::Red),
-                color::Bg(color::Reset),
-                color::Bg(color::Green),
-                color::Bg(color::Reset)
-            ));
+    backtrack_diff(expected, actual, &trace, max)
+}
+
+fn backtrack_diff<'a>(
+    expected: &[&'a str],
+    actual: &[&'a str],
+    trace: &[Vec<isize>],
+    max: isize,
+) -> Vec<LineDiffOp<'a>> {
+    let index = |k: isize| (k + max) as usize;
+    let mut x = expected.len() as isize;
+    let mut y = actual.len() as isize;
+    let mut ops = Vec::new();
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+
+        let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(LineDiffOp::Equal(expected[x as usize - 1]));
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            if x == prev_x {
+                ops.push(LineDiffOp::OnlyInActual(actual[y as usize - 1]));
+            } else {
+                ops.push(LineDiffOp::OnlyInExpected(expected[x as usize - 1]));
+            }
         }
+
+        x = prev_x;
+        y = prev_y;
     }
 
-    if correct {
-        CheckResult::Correct
-    } else {
-        CheckResult::Incorrect { message }
+    ops.reverse();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_tokens_reports_count_mismatch() {
+        let result = check_tokens("1 2 3", "1 2", None);
+        assert!(matches!(
+            result,
+            CheckResult::Incorrect { message }
+                if message.contains("expected 3 token(s), got 2")
+        ));
+    }
+
+    #[test]
+    fn check_tokens_exact_mode_does_not_tolerate_float_noise() {
+        assert!(matches!(
+            check_tokens("1.0", "1.0000001", None),
+            CheckResult::Incorrect { .. }
+        ));
+    }
+
+    #[test]
+    fn check_tokens_float_mode_tolerates_epsilon() {
+        assert!(matches!(
+            check_tokens("1.0", "1.0000001", Some(1e-3)),
+            CheckResult::Correct
+        ));
+    }
+
+    #[test]
+    fn tokens_equal_falls_back_to_exact_string_when_not_numeric() {
+        assert!(tokens_equal("abc", "abc", Some(1e-3)));
+        assert!(!tokens_equal("abc", "abd", Some(1e-3)));
+    }
+
+    #[test]
+    fn floats_close_rejects_nan() {
+        assert!(!floats_close(f64::NAN, f64::NAN, 1e-6));
+        assert!(!floats_close(f64::NAN, 1.0, 1e-6));
+    }
+
+    #[test]
+    fn floats_close_compares_infinities_by_equality() {
+        assert!(floats_close(f64::INFINITY, f64::INFINITY, 1e-6));
+        assert!(!floats_close(f64::INFINITY, f64::NEG_INFINITY, 1e-6));
+        assert!(!floats_close(f64::INFINITY, 1.0, 1e-6));
+    }
+
+    #[test]
+    fn floats_close_uses_absolute_or_relative_tolerance() {
+        assert!(floats_close(100.0, 100.0001, 1e-3));
+        assert!(!floats_close(100.0, 101.0, 1e-3));
+        assert!(floats_close(0.0, 1e-7, 1e-6));
+    }
+
+    #[test]
+    fn diff_lines_empty_vs_nonempty() {
+        let empty: [&str; 0] = [];
+        assert_eq!(diff_lines(&empty, &["a"]), vec![LineDiffOp::OnlyInActual("a")]);
+        assert_eq!(diff_lines(&["a"], &empty), vec![LineDiffOp::OnlyInExpected("a")]);
+    }
+
+    #[test]
+    fn diff_lines_single_insert_does_not_cascade() {
+        let expected = ["a", "b", "c"];
+        let actual = ["a", "x", "b", "c"];
+        assert_eq!(
+            diff_lines(&expected, &actual),
+            vec![
+                LineDiffOp::Equal("a"),
+                LineDiffOp::OnlyInActual("x"),
+                LineDiffOp::Equal("b"),
+                LineDiffOp::Equal("c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_lines_single_delete_does_not_cascade() {
+        let expected = ["a", "b", "c"];
+        let actual = ["a", "c"];
+        assert_eq!(
+            diff_lines(&expected, &actual),
+            vec![
+                LineDiffOp::Equal("a"),
+                LineDiffOp::OnlyInExpected("b"),
+                LineDiffOp::Equal("c"),
+            ]
+        );
     }
 }